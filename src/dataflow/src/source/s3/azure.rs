@@ -0,0 +1,153 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! [`ObjectStore`] implementation backed by Azure Blob Storage
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::header::CONTENT_ENCODING;
+use serde::Deserialize;
+
+use super::object_store::{ObjectMeta, ObjectStore, RangedRead};
+
+/// Lists and fetches blobs from a single Azure Blob Storage container.
+pub struct AzureStore {
+    pub http_client: reqwest::Client,
+    pub account: String,
+    pub container: String,
+    pub sas_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "EnumerationResults")]
+struct EnumerationResults {
+    #[serde(rename = "Blobs")]
+    blobs: Blobs,
+    #[serde(rename = "NextMarker")]
+    next_marker: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Blobs {
+    #[serde(rename = "Blob", default)]
+    blob: Vec<Blob>,
+}
+
+#[derive(Deserialize)]
+struct Blob {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Properties")]
+    properties: BlobProperties,
+}
+
+#[derive(Deserialize)]
+struct BlobProperties {
+    #[serde(rename = "Content-Length")]
+    content_length: u64,
+}
+
+/// Percent-encode a blob name for use in a URL path, the same way
+/// [`super::gcs::GcsStore`] encodes object names, while leaving `/`
+/// unescaped so a blob's virtual-directory hierarchy still becomes
+/// separate path segments rather than one opaque literal.
+fn encode_blob_path(key: &str) -> String {
+    key.split('/')
+        .map(|segment| {
+            percent_encoding::utf8_percent_encode(segment, percent_encoding::NON_ALPHANUMERIC)
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl AzureStore {
+    fn base_url(&self) -> String {
+        format!(
+            "https://{}.blob.core.windows.net/{}",
+            self.account, self.container
+        )
+    }
+
+    /// The SAS token is itself a ready-to-use query string (`se=...&sp=...&sig=...`),
+    /// so it's appended verbatim rather than re-encoded as a query parameter.
+    fn signed_url(&self, url: &str, extra_query: &str) -> String {
+        if extra_query.is_empty() {
+            format!("{}?{}", url, self.sas_token)
+        } else {
+            format!("{}?{}&{}", url, extra_query, self.sas_token)
+        }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for AzureStore {
+    async fn list(
+        &self,
+        prefix: Option<String>,
+    ) -> Result<BoxStream<'static, anyhow::Result<ObjectMeta>>, anyhow::Error> {
+        let http_client = self.http_client.clone();
+        let url = self.signed_url(&self.base_url(), "restype=container&comp=list");
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut marker = None;
+            loop {
+                let mut query = vec![];
+                if let Some(prefix) = &prefix {
+                    query.push(("prefix", prefix.clone()));
+                }
+                if let Some(marker) = &marker {
+                    query.push(("marker", marker.clone()));
+                }
+                let body = http_client
+                    .get(&url)
+                    .query(&query)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                let results: EnumerationResults = serde_xml_rs::from_str(&body)
+                    .map_err(|e| anyhow::anyhow!("unable to parse blob listing: {}", e))?;
+
+                for blob in results.blobs.blob {
+                    yield ObjectMeta { key: blob.name, size: blob.properties.content_length };
+                }
+
+                if results.next_marker.is_none() {
+                    break;
+                }
+                marker = results.next_marker;
+            }
+        }))
+    }
+
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<RangedRead, anyhow::Error> {
+        let url = self.signed_url(&format!("{}/{}", self.base_url(), encode_blob_path(key)), "");
+        let response = self
+            .http_client
+            .get(&url)
+            .header("Range", format!("bytes={}-{}", start, end))
+            .send()
+            .await?
+            .error_for_status()?;
+        let content_encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let stream = response
+            .bytes_stream()
+            .map(|r| r.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
+        Ok(RangedRead {
+            content_encoding,
+            reader: Box::new(tokio_util::io::StreamReader::new(stream)),
+        })
+    }
+}
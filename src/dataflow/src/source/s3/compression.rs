@@ -0,0 +1,110 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! Transparent streaming decompression for objects stored gzip/bzip2/zstd
+//! compressed, selected by the object's `Content-Encoding` response header
+//! if the provider sent one, falling back to the object key's suffix
+//! otherwise.
+
+use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, ZstdDecoder};
+use tokio::io::{AsyncBufRead, AsyncRead};
+
+/// The compression codec implied by an object's key suffix or
+/// `Content-Encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Gzip,
+    Bzip2,
+    Zstd,
+    None,
+}
+
+impl Codec {
+    fn from_key(key: &str) -> Codec {
+        if key.ends_with(".gz") {
+            Codec::Gzip
+        } else if key.ends_with(".bz2") {
+            Codec::Bzip2
+        } else if key.ends_with(".zst") {
+            Codec::Zstd
+        } else {
+            Codec::None
+        }
+    }
+
+    fn from_content_encoding(content_encoding: &str) -> Option<Codec> {
+        match content_encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Codec::Gzip),
+            "bzip2" | "x-bzip2" => Some(Codec::Bzip2),
+            "zstd" => Some(Codec::Zstd),
+            "identity" => Some(Codec::None),
+            // An encoding we don't recognize (or none at all): fall back to
+            // the key suffix rather than guessing.
+            _ => None,
+        }
+    }
+
+    /// Resolve the codec for an object, preferring its `Content-Encoding`
+    /// response header (the provider's own say on the wire format) over its
+    /// key suffix, since an object can be served with an encoding that its
+    /// key gives no hint of (e.g. a plain `.json` key uploaded gzip
+    /// encoded).
+    fn resolve(key: &str, content_encoding: Option<&str>) -> Codec {
+        content_encoding
+            .and_then(Codec::from_content_encoding)
+            .unwrap_or_else(|| Codec::from_key(key))
+    }
+}
+
+/// Wrap `reader` in the streaming decoder implied by `key` and
+/// `content_encoding`, so downstream line-framing can treat compressed and
+/// uncompressed objects identically.
+pub fn decompress<R>(key: &str, content_encoding: Option<&str>, reader: R) -> Box<dyn AsyncRead + Send + Unpin>
+where
+    R: AsyncBufRead + Send + Unpin + 'static,
+{
+    match Codec::resolve(key, content_encoding) {
+        Codec::Gzip => Box::new(GzipDecoder::new(reader)),
+        Codec::Bzip2 => Box::new(BzDecoder::new(reader)),
+        Codec::Zstd => Box::new(ZstdDecoder::new(reader)),
+        Codec::None => Box::new(reader),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_prefers_content_encoding_over_key_suffix() {
+        // No suffix at all -- this is the common case for objects uploaded
+        // with an explicit Content-Encoding and an extension-less key.
+        assert_eq!(Codec::resolve("data", Some("gzip")), Codec::Gzip);
+        assert_eq!(Codec::resolve("data", Some("x-gzip")), Codec::Gzip);
+        assert_eq!(Codec::resolve("data", Some("bzip2")), Codec::Bzip2);
+        assert_eq!(Codec::resolve("data", Some("zstd")), Codec::Zstd);
+
+        // A Content-Encoding that disagrees with the suffix wins.
+        assert_eq!(Codec::resolve("data.gz", Some("zstd")), Codec::Zstd);
+        assert_eq!(Codec::resolve("data.zst", Some("identity")), Codec::None);
+    }
+
+    #[test]
+    fn resolve_falls_back_to_key_suffix() {
+        assert_eq!(Codec::resolve("data.gz", None), Codec::Gzip);
+        assert_eq!(Codec::resolve("data.bz2", None), Codec::Bzip2);
+        assert_eq!(Codec::resolve("data.zst", None), Codec::Zstd);
+        assert_eq!(Codec::resolve("data.json", None), Codec::None);
+
+        // An unrecognized encoding value is treated the same as no
+        // Content-Encoding at all, rather than guessing.
+        assert_eq!(Codec::resolve("data.gz", Some("br")), Codec::Gzip);
+        assert_eq!(Codec::resolve("data.json", Some("br")), Codec::None);
+    }
+}
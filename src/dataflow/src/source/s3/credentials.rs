@@ -0,0 +1,142 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! Resolves AWS credentials for cloud object sources via the standard
+//! provider chain, with an optional STS `AssumeRole` exchange layered on
+//! top.
+//!
+//! Providers are tried in order, the same order the AWS SDKs use: explicit
+//! static credentials, environment variables, a named shared-config
+//! profile, the EKS/IRSA web-identity token flow, and finally the EC2/ECS
+//! instance-metadata service. This lets a Materialize deployment running in
+//! EKS or ECS read private buckets using its pod/instance IAM role instead
+//! of long-lived secrets embedded in SQL.
+
+use rusoto_core::credential::{
+    AutoRefreshingProvider, AwsCredentials, EnvironmentProvider, InstanceMetadataProvider,
+    ProfileProvider, ProvideAwsCredentials, StaticProvider,
+};
+use rusoto_core::{HttpClient, Region};
+use rusoto_s3::S3Client;
+use rusoto_sqs::SqsClient;
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient, WebIdentityProvider};
+
+use aws_util::aws::ConnectInfo;
+
+/// A type-erased credential provider, since each leg of the chain (and the
+/// optional `AssumeRole` wrapper) has a distinct concrete type.
+pub type BoxedProvider = Box<dyn ProvideAwsCredentials + Send + Sync>;
+
+struct LayeredProvider(Vec<BoxedProvider>);
+
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for LayeredProvider {
+    async fn credentials(
+        &self,
+    ) -> Result<AwsCredentials, rusoto_core::credential::CredentialsError> {
+        let mut last_err = None;
+        for provider in &self.0 {
+            match provider.credentials().await {
+                Ok(creds) => return Ok(creds),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            rusoto_core::credential::CredentialsError::new("no credential provider configured")
+        }))
+    }
+}
+
+/// Build the layered credential provider for `info`, optionally wrapping it
+/// in an `AssumeRole` exchange if `info.assume_role_arn` is set.
+pub fn resolve_provider(info: &ConnectInfo) -> Result<BoxedProvider, anyhow::Error> {
+    let mut providers: Vec<BoxedProvider> = Vec::new();
+
+    if let (Some(key), Some(secret)) = (&info.access_key_id, &info.secret_access_key) {
+        providers.push(Box::new(StaticProvider::new(
+            key.clone(),
+            secret.clone(),
+            info.session_token.clone(),
+            None,
+        )));
+    }
+
+    providers.push(Box::new(EnvironmentProvider::default()));
+
+    if let Some(profile) = &info.profile {
+        let mut profile_provider = ProfileProvider::new()?;
+        profile_provider.set_profile(profile.clone());
+        providers.push(Box::new(profile_provider));
+    }
+
+    if std::env::var_os("AWS_WEB_IDENTITY_TOKEN_FILE").is_some() {
+        providers.push(Box::new(WebIdentityProvider::from_k8s_env()));
+    }
+
+    // Tried last: the instance/task metadata service, present only inside
+    // EC2/ECS/EKS. IMDS lookups aren't region-scoped, so this needs no
+    // `Region` of its own.
+    providers.push(Box::new(InstanceMetadataProvider::new()));
+
+    let base: BoxedProvider = Box::new(LayeredProvider(providers));
+
+    match &info.assume_role_arn {
+        None => Ok(base),
+        Some(role_arn) => {
+            let sts_client = StsClient::new_with(
+                HttpClient::new()?,
+                DelegatingProvider(base),
+                Region::default(),
+            );
+            let assume_role = StsAssumeRoleSessionCredentialsProvider::new(
+                sts_client,
+                role_arn.clone(),
+                "materialize-s3".to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+            Ok(Box::new(AutoRefreshingProvider::new(assume_role)?))
+        }
+    }
+}
+
+/// Build an S3 client whose credentials come from the full provider chain
+/// (and, if configured, an `AssumeRole` exchange) rather than a single set
+/// of static keys.
+pub fn s3_client(info: &ConnectInfo) -> Result<S3Client, anyhow::Error> {
+    Ok(S3Client::new_with(
+        HttpClient::new()?,
+        resolve_provider(info)?,
+        info.region.clone(),
+    ))
+}
+
+/// Build an SQS client using the same resolved credentials as [`s3_client`].
+pub fn sqs_client(info: &ConnectInfo) -> Result<SqsClient, anyhow::Error> {
+    Ok(SqsClient::new_with(
+        HttpClient::new()?,
+        resolve_provider(info)?,
+        info.region.clone(),
+    ))
+}
+
+/// Adapts a `BoxedProvider` so it can be handed to `StsClient::new_with`,
+/// which wants an owned, `Sync` credential provider of its own.
+struct DelegatingProvider(BoxedProvider);
+
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for DelegatingProvider {
+    async fn credentials(
+        &self,
+    ) -> Result<AwsCredentials, rusoto_core::credential::CredentialsError> {
+        self.0.credentials().await
+    }
+}
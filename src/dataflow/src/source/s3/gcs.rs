@@ -0,0 +1,118 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! [`ObjectStore`] implementation backed by Google Cloud Storage
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::header::CONTENT_ENCODING;
+use serde::Deserialize;
+
+use super::object_store::{ObjectMeta, ObjectStore, RangedRead};
+
+const STORAGE_API_BASE: &str = "https://storage.googleapis.com/storage/v1";
+
+/// Lists and fetches objects from a single GCS bucket, via the JSON API.
+pub struct GcsStore {
+    pub http_client: reqwest::Client,
+    pub bucket: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize)]
+struct ListObjectsResponse {
+    items: Option<Vec<GcsObject>>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GcsObject {
+    name: String,
+    // The JSON API renders object size as a decimal string, not a number.
+    size: String,
+}
+
+impl GcsStore {
+    fn authed(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder.bearer_auth(&self.access_token)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for GcsStore {
+    async fn list(
+        &self,
+        prefix: Option<String>,
+    ) -> Result<BoxStream<'static, anyhow::Result<ObjectMeta>>, anyhow::Error> {
+        let http_client = self.http_client.clone();
+        let bucket = self.bucket.clone();
+        let access_token = self.access_token.clone();
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut page_token = None;
+            loop {
+                let url = format!("{}/b/{}/o", STORAGE_API_BASE, bucket);
+                let mut query = vec![];
+                if let Some(prefix) = &prefix {
+                    query.push(("prefix", prefix.clone()));
+                }
+                if let Some(page_token) = &page_token {
+                    query.push(("pageToken", page_token.clone()));
+                }
+                let response: ListObjectsResponse = http_client
+                    .get(&url)
+                    .bearer_auth(&access_token)
+                    .query(&query)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                for obj in response.items.unwrap_or_default() {
+                    let size = obj.size.parse().unwrap_or(0);
+                    yield ObjectMeta { key: obj.name, size };
+                }
+
+                if response.next_page_token.is_none() {
+                    break;
+                }
+                page_token = response.next_page_token;
+            }
+        }))
+    }
+
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<RangedRead, anyhow::Error> {
+        let url = format!(
+            "{}/b/{}/o/{}?alt=media",
+            STORAGE_API_BASE,
+            self.bucket,
+            percent_encoding::utf8_percent_encode(key, percent_encoding::NON_ALPHANUMERIC)
+        );
+        let response = self
+            .authed(self.http_client.get(&url))
+            .header("Range", format!("bytes={}-{}", start, end))
+            .send()
+            .await?
+            .error_for_status()?;
+        let content_encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let stream = response
+            .bytes_stream()
+            .map(|r| r.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
+        Ok(RangedRead {
+            content_encoding,
+            reader: Box::new(tokio_util::io::StreamReader::new(stream)),
+        })
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! A backend-agnostic interface over cloud object storage providers
+//!
+//! [`ObjectStore`] is implemented once per supported cloud (S3, GCS, Azure
+//! Blob), letting the rest of the source -- globbing, newline framing,
+//! offset tracking -- stay provider-agnostic.
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use tokio::io::AsyncRead;
+
+/// Metadata about a single object returned by a [`ObjectStore::list`] call.
+#[derive(Clone)]
+pub struct ObjectMeta {
+    /// The object's key (path within the bucket/container).
+    pub key: String,
+    /// The object's size in bytes, as reported by the provider's listing
+    /// response. Used to compute byte-range download windows.
+    pub size: u64,
+}
+
+/// The result of a [`ObjectStore::get_range`] call.
+pub struct RangedRead {
+    /// The provider's `Content-Encoding` response header, if it reported
+    /// one. Objects uploaded with an encoding set this way are commonly
+    /// stored under a key with no suffix hinting at the compression (e.g. a
+    /// plain `.json` key served gzip-encoded), so this is the only place
+    /// that information is still available -- it's read from the response
+    /// that starts the download, not re-derived from the key later.
+    pub content_encoding: Option<String>,
+    pub reader: Box<dyn AsyncRead + Send + Unpin>,
+}
+
+/// A cloud object storage backend that can list and fetch objects.
+///
+/// Implementations should paginate internally and yield objects lazily via
+/// the returned stream, rather than buffering an entire bucket listing.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// List all objects whose key begins with `prefix`.
+    async fn list(
+        &self,
+        prefix: Option<String>,
+    ) -> Result<BoxStream<'static, anyhow::Result<ObjectMeta>>, anyhow::Error>;
+
+    /// Fetch the inclusive byte range `start..=end` of `key` as a streaming
+    /// reader, via an HTTP `Range` request. Callers page through an object
+    /// in bounded windows rather than issuing one GET sized by its full
+    /// length.
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<RangedRead, anyhow::Error>;
+}
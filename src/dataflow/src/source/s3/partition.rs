@@ -0,0 +1,188 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! Distributes discovered object keys across the timely workers of a single
+//! source instance, so that listing (done by one coordinator worker) and
+//! downloading (done by every worker) can proceed in parallel.
+//!
+//! Workers of the same dataflow run in threads of one process, so a small
+//! process-global table of per-worker channels is enough to hand a key from
+//! the coordinator to whichever worker is about to download it -- no actual
+//! network hop is involved.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use expr::SourceInstanceId;
+
+use super::object_store::ObjectMeta;
+
+/// An object handed from a coordinator (bucket listing or SQS polling) to
+/// the worker assigned to download it.
+///
+/// `ack`, when present, is fired once the assigned worker has finished
+/// downloading and emitting the object, so a sender that needs the
+/// handoff to be durable (e.g. before deleting the SQS message that
+/// announced the object) can wait on it rather than treating enqueueing
+/// as completion.
+pub struct QueuedObject {
+    pub meta: ObjectMeta,
+    pub ack: Option<oneshot::Sender<()>>,
+}
+
+type KeySender = mpsc::UnboundedSender<QueuedObject>;
+type Slot = watch::Sender<Option<KeySender>>;
+
+/// One row per worker of a source instance; each row is filled in once that
+/// worker has registered its key-intake channel.
+pub struct PartitionTable {
+    slots: Vec<Slot>,
+    /// Workers still holding a handle to this table; the entry in `TABLES`
+    /// is removed once this reaches zero, so a dropped source doesn't leak
+    /// its table for the rest of the process's life.
+    live_workers: AtomicUsize,
+}
+
+static TABLES: Lazy<Mutex<HashMap<SourceInstanceId, std::sync::Arc<PartitionTable>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Look up (or lazily create) the partition table shared by all workers of
+/// `source_id`.
+pub fn table_for(
+    source_id: SourceInstanceId,
+    worker_count: usize,
+) -> std::sync::Arc<PartitionTable> {
+    TABLES
+        .lock()
+        .expect("partition table lock poisoned")
+        .entry(source_id)
+        .or_insert_with(|| {
+            std::sync::Arc::new(PartitionTable {
+                slots: (0..worker_count).map(|_| watch::channel(None).0).collect(),
+                live_workers: AtomicUsize::new(worker_count),
+            })
+        })
+        .clone()
+}
+
+/// Release this worker's handle on `source_id`'s table, removing it from
+/// `TABLES` once every worker of the source has done so.
+pub fn deregister(source_id: &SourceInstanceId, table: &PartitionTable) {
+    if table.live_workers.fetch_sub(1, Ordering::SeqCst) == 1 {
+        TABLES
+            .lock()
+            .expect("partition table lock poisoned")
+            .remove(source_id);
+    }
+}
+
+impl PartitionTable {
+    /// Register this worker's key-intake channel so the coordinator can
+    /// find it.
+    pub fn register(&self, worker_id: usize, tx: KeySender) {
+        // `watch::Sender::send` only stores a value while a receiver is
+        // alive, so hold one across the send: the value then lands in the
+        // watch cell for later `wait_for` subscribers to observe even if
+        // nobody is waiting yet.
+        let _rx = self.slots[worker_id].subscribe();
+        let _ = self.slots[worker_id].send(Some(tx));
+    }
+
+    /// Return the key-intake sender for `worker_id`, waiting for that
+    /// worker to register if it hasn't yet (workers start up independently
+    /// and in no particular order).
+    pub async fn wait_for(&self, worker_id: usize) -> KeySender {
+        let mut rx = self.slots[worker_id].subscribe();
+        loop {
+            if let Some(tx) = rx.borrow().clone() {
+                return tx;
+            }
+            if rx.changed().await.is_err() {
+                panic!("partition table slot {} dropped before registering", worker_id);
+            }
+        }
+    }
+
+    /// The number of workers participating in this source.
+    pub fn worker_count(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+/// Build a standalone [`PartitionTable`], bypassing the process-global
+/// registry in [`TABLES`], for tests in sibling modules that need a table to
+/// register workers against without a real `SourceInstanceId`.
+#[cfg(test)]
+pub(crate) fn table_for_test(worker_count: usize) -> PartitionTable {
+    PartitionTable {
+        slots: (0..worker_count).map(|_| watch::channel(None).0).collect(),
+        live_workers: AtomicUsize::new(worker_count),
+    }
+}
+
+/// Hash `key` to a worker index in `[0, worker_count)`.
+pub fn assign_worker(key: &str, worker_count: usize) -> usize {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() as usize) % worker_count
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assign_worker_is_deterministic_and_in_range() {
+        for key in &["foo.json", "a/b/c.csv.gz", ""] {
+            let first = assign_worker(key, 4);
+            assert!(first < 4);
+            assert_eq!(first, assign_worker(key, 4));
+        }
+    }
+
+    #[test]
+    fn assign_worker_spreads_across_workers() {
+        let mut targets = std::collections::HashSet::new();
+        for i in 0..100 {
+            targets.insert(assign_worker(&format!("key-{}", i), 8));
+        }
+        assert!(targets.len() > 1, "all keys hashed to the same worker");
+    }
+
+    #[tokio::test]
+    async fn wait_for_returns_the_registered_sender() {
+        let slots: Vec<Slot> = (0..2).map(|_| watch::channel(None).0).collect();
+        let table = PartitionTable {
+            slots,
+            live_workers: AtomicUsize::new(2),
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        table.register(1, tx);
+
+        let registered = table.wait_for(1).await;
+        registered
+            .send(QueuedObject {
+                meta: ObjectMeta {
+                    key: "foo".to_string(),
+                    size: 0,
+                },
+                ack: None,
+            })
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().meta.key, "foo");
+    }
+}
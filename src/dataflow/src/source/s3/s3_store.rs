@@ -0,0 +1,95 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! [`ObjectStore`] implementation backed by Amazon S3
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use rusoto_s3::{GetObjectRequest, ListObjectsV2Request, S3Client, S3};
+
+use super::object_store::{ObjectMeta, ObjectStore, RangedRead};
+
+/// Lists and fetches objects from a single S3 bucket.
+pub struct S3Store {
+    pub client: S3Client,
+    pub bucket: String,
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn list(
+        &self,
+        prefix: Option<String>,
+    ) -> Result<BoxStream<'static, anyhow::Result<ObjectMeta>>, anyhow::Error> {
+        let client = self.client.clone();
+        let bucket = self.bucket.clone();
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut continuation_token = None;
+            let mut allowed_errors = 10;
+            loop {
+                let response = client
+                    .list_objects_v2(ListObjectsV2Request {
+                        bucket: bucket.clone(),
+                        prefix: prefix.clone(),
+                        continuation_token: continuation_token.clone(),
+                        ..Default::default()
+                    })
+                    .await;
+
+                match response {
+                    Ok(response) => {
+                        allowed_errors = 10;
+                        for obj in response.contents.unwrap_or_default() {
+                            if let Some(key) = obj.key {
+                                let size = obj.size.unwrap_or(0) as u64;
+                                yield ObjectMeta { key, size };
+                            }
+                        }
+                        if response.next_continuation_token.is_none() {
+                            break;
+                        }
+                        continuation_token = response.next_continuation_token;
+                    }
+                    Err(e) => {
+                        allowed_errors -= 1;
+                        if allowed_errors == 0 {
+                            Err(anyhow::anyhow!("failed to list bucket {}: {}", bucket, e))?;
+                            break;
+                        }
+                        log::warn!(
+                            "unable to list bucket {}: {} ({} retries remaining)",
+                            bucket, e, allowed_errors
+                        );
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<RangedRead, anyhow::Error> {
+        let obj = self
+            .client
+            .get_object(GetObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                range: Some(format!("bytes={}-{}", start, end)),
+                ..Default::default()
+            })
+            .await?;
+        let content_encoding = obj.content_encoding.clone();
+        let body = obj
+            .body
+            .ok_or_else(|| anyhow::anyhow!("get object response for {} had no body", key))?;
+        Ok(RangedRead {
+            content_encoding,
+            reader: Box::new(body.into_async_read()),
+        })
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Use of this software is governed by the Business Source License
+// included in the LICENSE file.
+//
+// As of the Change Date specified in that file, in accordance with
+// the Business Source License, use of this software will be governed
+// by the Apache License, Version 2.0.
+
+//! Continuous ingestion of S3 objects via SQS event notifications
+
+use std::collections::HashMap;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+
+use anyhow::anyhow;
+use globset::GlobMatcher;
+use rusoto_sqs::{DeleteMessageRequest, Message, ReceiveMessageRequest, Sqs, SqsClient};
+use serde::Deserialize;
+use tokio::time::{self, Duration};
+
+use super::object_store::ObjectMeta;
+use super::partition::{self, PartitionTable, QueuedObject};
+
+/// How long a `ReceiveMessage` call is allowed to block waiting for a message.
+const WAIT_TIME_SECONDS: i64 = 20;
+/// How long we ask SQS to hide a message from other receivers while we process it.
+const VISIBILITY_TIMEOUT_SECONDS: i64 = 120;
+
+/// Long-poll `queue` for S3 event notifications, handing newly created
+/// objects that match `glob` off to their hash-assigned worker the same way
+/// [`super::coordinate_listing`] does.
+///
+/// A message is only deleted from the queue once every object it announced
+/// has actually been downloaded and emitted by its assigned worker (tracked
+/// via an ack channel threaded through the handoff), so a crash any time
+/// before that -- including mid-download -- leaves the message to be
+/// redelivered (and the object re-ingested) once its visibility timeout
+/// expires.
+pub async fn poll_sqs_notifications(
+    queue: String,
+    glob: Option<GlobMatcher>,
+    sqs_client: SqsClient,
+    table: Arc<PartitionTable>,
+    worker_count: usize,
+    tx: SyncSender<anyhow::Result<Vec<u8>>>,
+) {
+    let glob = glob.as_ref();
+    // Keys we've already processed within the current visibility window, so
+    // that at-least-once SQS delivery doesn't re-ingest an object we're
+    // already in the middle of handling.
+    let mut seen: HashMap<(String, String), Instant> = HashMap::new();
+
+    loop {
+        let response = sqs_client
+            .receive_message(ReceiveMessageRequest {
+                queue_url: queue.clone(),
+                max_number_of_messages: Some(10),
+                wait_time_seconds: Some(WAIT_TIME_SECONDS),
+                visibility_timeout: Some(VISIBILITY_TIMEOUT_SECONDS),
+                ..Default::default()
+            })
+            .await;
+
+        let messages = match response {
+            Ok(response) => response.messages.unwrap_or_default(),
+            Err(e) => {
+                log::warn!("unable to receive messages from queue {}: {}", queue, e);
+                time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        prune_seen(&mut seen);
+
+        for message in messages {
+            handle_message(
+                &queue,
+                glob,
+                &sqs_client,
+                &table,
+                worker_count,
+                &tx,
+                &mut seen,
+                message,
+            )
+            .await;
+        }
+    }
+}
+
+async fn handle_message(
+    queue: &str,
+    glob: Option<&GlobMatcher>,
+    sqs_client: &SqsClient,
+    table: &PartitionTable,
+    worker_count: usize,
+    tx: &SyncSender<anyhow::Result<Vec<u8>>>,
+    seen: &mut HashMap<(String, String), Instant>,
+    message: Message,
+) {
+    let body = match &message.body {
+        Some(body) => body,
+        None => {
+            log::warn!("sqs message from {} had no body", queue);
+            return;
+        }
+    };
+
+    let records = match parse_event(body) {
+        Ok(records) => records,
+        Err(e) => {
+            tx.send(Err(anyhow!("unable to parse s3 event notification: {}", e)))
+                .unwrap_or_else(|e| log::debug!("unable to send error on stream: {}", e));
+            return;
+        }
+    };
+
+    let acks = dispatch_records(glob, table, worker_count, seen, records).await;
+
+    for ack in acks {
+        if ack.await.is_err() {
+            log::warn!(
+                "worker dropped its ack channel before finishing an object from {}; \
+                 leaving the message to be redelivered",
+                queue
+            );
+            return;
+        }
+    }
+
+    delete_message(queue, sqs_client, message).await;
+}
+
+/// Filter `records` down to object-creation events matching `glob`, hand each
+/// off to its hash-assigned worker, and return an ack receiver per object
+/// that was successfully handed off.
+///
+/// Acks for every object we hand off from this message; the message is only
+/// deleted once all of them have reported their download done, so a crash
+/// mid-download redelivers the message instead of losing the object.
+async fn dispatch_records(
+    glob: Option<&GlobMatcher>,
+    table: &PartitionTable,
+    worker_count: usize,
+    seen: &mut HashMap<(String, String), Instant>,
+    records: Vec<S3EventRecord>,
+) -> Vec<tokio::sync::oneshot::Receiver<()>> {
+    let mut acks = Vec::new();
+
+    for record in records {
+        // Bucket notification configs commonly also deliver
+        // `ObjectRemoved:*`, restore and replication events; only object
+        // creation is something we know how to download. Note the delivered
+        // `eventName` has no `s3:` prefix -- that prefix only appears in the
+        // bucket notification *configuration*, not the message itself.
+        if !record.event_name.starts_with("ObjectCreated:") {
+            continue;
+        }
+        let bucket = record.s3.bucket.name;
+        let key = match percent_encoding::percent_decode_str(&record.s3.object.key).decode_utf8()
+        {
+            Ok(key) => key.into_owned(),
+            Err(e) => {
+                log::warn!("unable to percent-decode object key: {}", e);
+                continue;
+            }
+        };
+
+        if !glob.map(|g| g.is_match(&key)).unwrap_or(true) {
+            continue;
+        }
+
+        if seen.contains_key(&(bucket.clone(), key.clone())) {
+            continue;
+        }
+
+        let object = ObjectMeta {
+            key: key.clone(),
+            size: record.s3.object.size,
+        };
+        let target = partition::assign_worker(&object.key, worker_count);
+        let key_tx = table.wait_for(target).await;
+        let (ack_tx, ack_rx) = tokio::sync::oneshot::channel();
+        if let Err(e) = key_tx.send(QueuedObject {
+            meta: object,
+            ack: Some(ack_tx),
+        }) {
+            log::trace!("worker {} key channel dropped: {}", target, e);
+        } else {
+            acks.push(ack_rx);
+        }
+        seen.insert((bucket, key), Instant::now());
+    }
+
+    acks
+}
+
+async fn delete_message(queue: &str, sqs_client: &SqsClient, message: Message) {
+    let receipt_handle = match message.receipt_handle {
+        Some(receipt_handle) => receipt_handle,
+        None => return,
+    };
+    if let Err(e) = sqs_client
+        .delete_message(DeleteMessageRequest {
+            queue_url: queue.to_string(),
+            receipt_handle,
+        })
+        .await
+    {
+        log::warn!("unable to delete message from queue {}: {}", queue, e);
+    }
+}
+
+/// Drop keys from the dedup set once they've aged out of the visibility
+/// window, so a genuinely re-created object with the same key can be
+/// ingested again later.
+fn prune_seen(seen: &mut HashMap<(String, String), Instant>) {
+    let window = StdDuration::from_secs(VISIBILITY_TIMEOUT_SECONDS as u64);
+    seen.retain(|_, seen_at| seen_at.elapsed() < window);
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventNotificationEnvelope {
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventRecord {
+    #[serde(rename = "eventName")]
+    event_name: String,
+    s3: S3EventDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventDetail {
+    bucket: S3EventBucket,
+    object: S3EventObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventBucket {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3EventObject {
+    key: String,
+    #[serde(default)]
+    size: u64,
+}
+
+fn parse_event(body: &str) -> Result<Vec<S3EventRecord>, serde_json::Error> {
+    let envelope: S3EventNotificationEnvelope = serde_json::from_str(body)?;
+    Ok(envelope.records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(event_name: &str) -> S3EventRecord {
+        S3EventRecord {
+            event_name: event_name.to_string(),
+            s3: S3EventDetail {
+                bucket: S3EventBucket {
+                    name: "my-bucket".to_string(),
+                },
+                object: S3EventObject {
+                    key: "foo.json".to_string(),
+                    size: 123,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn parse_event_reads_records() {
+        let body = r#"{"Records":[{"eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"my-bucket"},"object":{"key":"foo.json","size":123}}}]}"#;
+        let records = parse_event(body).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].s3.bucket.name, "my-bucket");
+        assert_eq!(records[0].s3.object.key, "foo.json");
+        assert_eq!(records[0].s3.object.size, 123);
+    }
+
+    #[test]
+    fn parse_event_missing_records_is_empty() {
+        let records = parse_event(r#"{}"#).unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn only_object_created_events_pass_the_filter() {
+        // Delivered `eventName` values have no `s3:` prefix -- that prefix
+        // only appears in the bucket notification *configuration*.
+        let passes = |name: &str| record(name).event_name.starts_with("ObjectCreated:");
+        assert!(passes("ObjectCreated:Put"));
+        assert!(passes("ObjectCreated:CompleteMultipartUpload"));
+        assert!(!passes("TestEvent"));
+        assert!(!passes("ObjectRemoved:Delete"));
+        assert!(!passes("ObjectRestore:Completed"));
+    }
+
+    #[tokio::test]
+    async fn dispatch_records_hands_off_only_real_object_created_events() {
+        // A realistic SQS envelope: the kind of body S3 actually delivers,
+        // eventName included, mixed in with event types we should ignore.
+        let body = r#"{"Records":[
+            {"eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"my-bucket"},"object":{"key":"foo.json","size":123}}},
+            {"eventName":"ObjectRemoved:Delete","s3":{"bucket":{"name":"my-bucket"},"object":{"key":"bar.json","size":456}}},
+            {"eventName":"TestEvent","s3":{"bucket":{"name":"my-bucket"},"object":{"key":"baz.json","size":0}}}
+        ]}"#;
+        let records = parse_event(body).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let table = partition::table_for_test(1);
+        let (key_tx, mut key_rx) = tokio::sync::mpsc::unbounded_channel();
+        table.register(0, key_tx);
+
+        let mut seen = HashMap::new();
+        let acks = dispatch_records(None, &table, 1, &mut seen, records).await;
+        assert_eq!(acks.len(), 1, "only the ObjectCreated record should be handed off");
+
+        let queued = key_rx.try_recv().unwrap();
+        assert_eq!(queued.meta.key, "foo.json");
+        assert_eq!(queued.meta.size, 123);
+        assert!(key_rx.try_recv().is_err(), "no other record should be queued");
+    }
+}